@@ -1,8 +1,15 @@
-use std::{path::PathBuf, time};
+use std::{
+    os::fd::{FromRawFd, OwnedFd},
+    path::PathBuf,
+    time,
+};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use clap::Parser;
-use tracing::{debug, info};
+use hyper::{client, http};
+use rand::Rng;
+use tokio::io::{unix::AsyncFd, Interest};
+use tracing::{debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 
 #[derive(Parser)]
@@ -17,9 +24,13 @@ struct Args {
     /// Log level
     log_level: EnvFilter,
 
-    /// Name of the process to watch state for
-    #[clap(short, long)]
-    proc_name: String,
+    /// `comm` of the application process to watch
+    #[clap(long, env = "PROC_WATCHER_APP_COMM")]
+    app_comm: String,
+
+    /// `comm` of the linkerd-proxy sidecar process to watch
+    #[clap(long, env = "PROC_WATCHER_PROXY_COMM", default_value = "linkerd-proxy")]
+    proxy_comm: String,
 
     /// Timeout value when waiting to get pid based on process name
     #[clap(parse(try_from_str = parse_timeout), long, default_value = "300s")]
@@ -28,63 +39,293 @@ struct Args {
     /// Backoff value when retrying to get pid based on process name
     #[clap(parse(try_from_str = parse_timeout), long, default_value = "120s")]
     pid_watch_backoff: time::Duration,
+
+    /// Port the proxy's admin server (and its `/shutdown` endpoint) listens on
+    #[clap(long, env = "PROC_WATCHER_PROXY_ADMIN_PORT", default_value = "4191")]
+    proxy_admin_port: u16,
+
+    /// Timeout for a single proxy shutdown request
+    #[clap(parse(try_from_str = parse_timeout), long, default_value = "1s")]
+    shutdown_timeout: time::Duration,
+
+    /// Maximum number of retries before giving up on the proxy shutdown request
+    #[clap(long, default_value = "5")]
+    shutdown_max_retries: u32,
 }
 
-// TODO: will need tokio here
-// will have to watch for ctrl-c signal, or something similar.
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     let Args {
         log_level,
-        proc_name,
+        app_comm,
+        proxy_comm,
         pid_watch_timeout,
         pid_watch_backoff,
+        proxy_admin_port,
+        shutdown_timeout,
+        shutdown_max_retries,
     } = Args::parse();
     tracing_subscriber::fmt()
         .with_env_filter(log_level)
         .try_init()
         .map_err(|err| anyhow!("Failed to initialize tracing subscriber: {}", err))?;
 
-    let v = watch_process();
-    Ok(())
+    let config = ShutdownConfig {
+        admin_port: proxy_admin_port,
+        timeout: shutdown_timeout,
+        max_retries: shutdown_max_retries,
+    };
+
+    let status = run(
+        app_comm,
+        proxy_comm,
+        pid_watch_timeout,
+        pid_watch_backoff,
+        config,
+    )
+    .await?;
+    std::process::exit(status);
+}
+
+/// Wait for the app process to exit, then tell the linkerd-proxy sidecar to
+/// shut down so the pod can complete, mirroring what the injected
+/// `linkerd-await` wrapper does for processes we don't control directly.
+async fn run(
+    app_comm: String,
+    proxy_comm: String,
+    pid_watch_timeout: time::Duration,
+    pid_watch_backoff: time::Duration,
+    shutdown_config: ShutdownConfig,
+) -> Result<i32> {
+    let procs = discover_processes(&proxy_comm, &app_comm, pid_watch_timeout, pid_watch_backoff).await?;
+    let app = procs
+        .iter()
+        .find_map(|prc| match prc {
+            Proc::App(info) => Some(info),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("no app process ({}) found", app_comm))?;
+    if !procs.iter().any(|p| matches!(p, Proc::Proxy(_))) {
+        debug!(%proxy_comm, "proxy process not found yet, will shut it down via its admin port regardless");
+    }
+
+    wait_for_exit(app.0).await?;
+    info!(%app, "app process exited");
+
+    // We didn't spawn the app as a child, so we have no `waitpid(2)` handle
+    // on it and can't recover its real exit status. Mirroring the app's
+    // exit code would require a channel we don't have here (e.g. reading
+    // the container's terminated status from the Kubernetes API, which this
+    // binary has no client for) -- so this is intentionally out of scope:
+    // our own exit code reflects whether *we* completed our job (observing
+    // the app exit and notifying the proxy), not the app's outcome.
+    let app_status = 0;
+
+    match send_shutdown(shutdown_config).await {
+        Ok(status) => info!(%status, "proxy shutdown request succeeded"),
+        Err(error) => {
+            warn!(%error, "giving up on proxy shutdown request");
+            return Ok(1);
+        }
+    }
+
+    Ok(app_status)
+}
+
+/// Shutdown timeout/retry knobs for the proxy admin request.
+struct ShutdownConfig {
+    admin_port: u16,
+    timeout: time::Duration,
+    max_retries: u32,
 }
 
-// TODO: need a timer so we avoid using too much cpu
-// TODO: use inotify to get notified when the process is deleted after we
-// establish which process we're looking for
-async fn run(app_comm: String, proxy_comm: String) -> Result<()> {
-    unimplemented!();
+/// POST `/shutdown` to the proxy's admin endpoint, retrying transient
+/// failures (timeouts and non-2xx responses) with exponential backoff and
+/// jitter. A proxy that's already gone (connection refused) is treated as
+/// already shut down rather than an error.
+async fn send_shutdown(config: ShutdownConfig) -> Result<http::StatusCode> {
+    let client = client::Client::new();
+    let uri = hyper::Uri::builder()
+        .scheme(http::uri::Scheme::HTTP)
+        .authority(format!("localhost:{}", config.admin_port))
+        .path_and_query("/shutdown")
+        .build()
+        .expect("shutdown uri must be valid");
+
+    let mut attempt = 0u32;
+    loop {
+        let req = http::Request::builder()
+            .method(http::Method::POST)
+            .uri(uri.clone())
+            .body(Default::default())
+            .expect("shutdown request must be valid");
+
+        debug!(%uri, attempt, "sending proxy shutdown request");
+        match tokio::time::timeout(config.timeout, client.request(req)).await {
+            Ok(Ok(resp)) if resp.status().is_success() => return Ok(resp.status()),
+            Ok(Ok(resp)) => {
+                debug!(%uri, status = %resp.status(), attempt, "proxy shutdown request failed")
+            }
+            Ok(Err(error)) if error.is_connect() => {
+                // Nothing listening on the admin port: the proxy has already
+                // exited (or never started), so there's nothing left to do.
+                info!(%uri, "proxy already gone, nothing to shut down");
+                return Ok(http::StatusCode::OK);
+            }
+            Ok(Err(error)) => debug!(%uri, %error, attempt, "proxy shutdown request errored"),
+            Err(_) => debug!(%uri, ?config.timeout, attempt, "proxy shutdown request timed out"),
+        }
+
+        if attempt >= config.max_retries {
+            return Err(anyhow!(
+                "exhausted {} retries sending shutdown request to {}",
+                config.max_retries,
+                uri
+            ));
+        }
+        attempt += 1;
+
+        let backoff = config.timeout.saturating_mul(1 << attempt.min(6));
+        let jitter = rand::thread_rng().gen_range(time::Duration::ZERO..=config.timeout);
+        let backoff = backoff
+            .saturating_add(jitter)
+            .min(time::Duration::from_secs(30));
+        debug!(?backoff, attempt, "backing off before retry");
+        tokio::time::sleep(backoff).await;
+    }
 }
 
-// Check comm (command name)
-fn watch_process(proxy_comm: &str, app_comm: &str) -> Vec<Proc> {
-    let mut found_proxy = false;
-    let mut found_app = false;
-    let mut prcs = Vec::new();
-    while !found_proxy && !found_app {
-        debug!(%proxy_comm, %app_comm, "Watching processes");
-        let mut procs = procfs::process::all_processes()
-            .expect("Failed to list processes")
-            .into_iter();
-        while let Some(prc) = procs.next() {
-            let stat = prc.stat().expect("Failed to read prc stat");
+/// Scan `/proc` for the app and proxy processes by `comm`, sleeping
+/// `backoff` between scans and giving up once `timeout` has elapsed. This
+/// replaces the old busy-poll loop, which never slept and pinned a CPU core.
+///
+/// Only the app process gates the wait: the proxy shutdown request goes to
+/// its admin port over localhost, not its pid, so a proxy that hasn't
+/// started yet (or whose `comm` doesn't match) shouldn't block discovery.
+/// The proxy is still recorded when seen, for logging.
+async fn discover_processes(
+    proxy_comm: &str,
+    app_comm: &str,
+    timeout: time::Duration,
+    backoff: time::Duration,
+) -> Result<Vec<Proc>> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        debug!(%proxy_comm, %app_comm, "scanning processes");
+        let mut found = Vec::new();
+        for prc in procfs::process::all_processes().context("failed to list processes")? {
+            let stat = match prc.and_then(|p| p.stat()) {
+                Ok(stat) => stat,
+                Err(error) => {
+                    debug!(%error, "failed to read process stat, skipping");
+                    continue;
+                }
+            };
             if stat.comm == proxy_comm {
-                let prc_info = ProcInfo::new(stat.pid, stat.comm);
-                debug!(%prc_info, "Found proxy process stat");
-                let p = Proc::Proxy(prc_info);
-                found_proxy = true;
-                prcs.push(p);
+                let info = ProcInfo::new(stat.pid, stat.comm);
+                debug!(%info, "found proxy process");
+                found.push(Proc::Proxy(info));
             } else if stat.comm == app_comm {
-                let prc_info = ProcInfo::new(stat.pid, stat.comm);
-                debug!(%prc_info, "Found app process stat");
-                let p = Proc::App(prc_info);
-                found_app = true;
-                prcs.push(p);
+                let info = ProcInfo::new(stat.pid, stat.comm);
+                debug!(%info, "found app process");
+                found.push(Proc::App(info));
             }
         }
+
+        let found_app = found.iter().any(|p| matches!(p, Proc::App(_)));
+        if found_app {
+            return Ok(found);
+        }
+
+        if tokio::time::Instant::now() >= deadline {
+            return Err(anyhow!(
+                "timed out after {:?} waiting for app process ({})",
+                timeout,
+                app_comm
+            ));
+        }
+
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+/// Wait for `pid` to exit. Prefers an event-driven `pidfd` wait (fires the
+/// instant the kernel reaps the process); falls back to watching `/proc/<pid>`
+/// for removal where `pidfd_open(2)` isn't available.
+async fn wait_for_exit(pid: i32) -> Result<()> {
+    match open_pidfd(pid) {
+        Ok(fd) => wait_for_pidfd_readable(fd).await,
+        Err(error) if error.raw_os_error() == Some(libc::ESRCH) => {
+            // The process is already gone by the time we tried to open its
+            // pidfd -- nothing to wait for.
+            debug!(pid, "process already exited before pidfd could be opened");
+            Ok(())
+        }
+        Err(error) => {
+            warn!(pid, %error, "pidfd_open unavailable, falling back to /proc polling");
+            wait_for_proc_removed(pid).await
+        }
+    }
+}
+
+/// Open a pidfd for `pid` via the `pidfd_open(2)` syscall.
+fn open_pidfd(pid: i32) -> std::io::Result<OwnedFd> {
+    // SAFETY: pidfd_open(2) takes a pid and a flags word (0 here); the
+    // returned fd is owned by us on success.
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as std::os::fd::RawFd) })
+}
+
+/// A pidfd becomes readable exactly when the process it refers to
+/// terminates, so registering it with Tokio's reactor turns "wait for exit"
+/// into a plain `await` instead of a polling loop.
+async fn wait_for_pidfd_readable(fd: OwnedFd) -> Result<()> {
+    let async_fd = AsyncFd::with_interest(fd, Interest::READABLE)
+        .context("failed to register pidfd with the async runtime")?;
+    let mut guard = async_fd
+        .readable()
+        .await
+        .context("failed to wait on pidfd")?;
+    guard.clear_ready();
+    Ok(())
+}
+
+/// Fallback for kernels without `pidfd_open(2)`: watch `/proc/<pid>` itself
+/// via inotify and wait for it to disappear. Handles the race where the process has
+/// already exited by checking existence up front.
+async fn wait_for_proc_removed(pid: i32) -> Result<()> {
+    use notify::{RecursiveMode, Watcher};
+
+    let proc_path = PathBuf::from(format!("/proc/{pid}"));
+    if !proc_path.exists() {
+        debug!(pid, "process already exited");
+        return Ok(());
     }
 
-    prcs
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(tx).context("failed to create /proc watcher")?;
+    watcher
+        .watch(&proc_path, RecursiveMode::NonRecursive)
+        .context("failed to watch /proc/<pid>")?;
+
+    tokio::task::spawn_blocking(move || {
+        // The watch target disappearing (rather than any particular event
+        // kind) is the signal we care about; /proc entries don't always
+        // report a clean Remove event, so poll existence on every wakeup.
+        for _ in rx {
+            if !proc_path.exists() {
+                break;
+            }
+        }
+    })
+    .await
+    .context("/proc watcher task panicked")?;
+
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -5,7 +5,7 @@ use futures::future::BoxFuture;
 use hyper::body::Buf;
 use hyper::{http, service::Service, Body, Request, Response};
 use json_patch::PatchOperation;
-use k8s_openapi::api::batch::v1::JobSpec;
+use k8s_openapi::api::batch::v1::{Job, JobSpec};
 use k8s_openapi::api::core::v1::{Pod, PodSpec};
 use kube::core::ObjectMeta;
 use kube::{
@@ -15,10 +15,75 @@ use kube::{
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::convert::{TryFrom, TryInto};
+use std::sync::Arc;
 use tracing::{debug, debug_span, trace, Instrument};
 
+use crate::metrics::{Metrics, SkipReason};
+
+/// Configuration for the sidecar this webhook injects: the image the
+/// `linkerd-await` binary is copied from (and its pull policy), the path
+/// it's copied to, the name of the already-injected proxy container
+/// (skipped when patching app containers), and the proxy admin port the
+/// copied binary should signal on shutdown.
+#[derive(clap::Args, Debug, Clone)]
+pub struct InjectionConfig {
+    /// Image used for the init container that copies `linkerd-await` in
+    #[clap(
+        long,
+        env = "LINKERD_SWEEP_AWAIT_IMAGE",
+        default_value = "ghcr.io/mateiidavid/await-util:test"
+    )]
+    pub await_image: String,
+
+    /// Pull policy for the `await_image` init container
+    #[clap(
+        long,
+        env = "LINKERD_SWEEP_AWAIT_IMAGE_PULL_POLICY",
+        default_value = "IfNotPresent"
+    )]
+    pub await_image_pull_policy: String,
+
+    /// Path the `linkerd-await` volume is mounted at; the binary is copied to
+    /// and run from `<path>/linkerd-await`
+    #[clap(
+        long,
+        env = "LINKERD_SWEEP_AWAIT_MOUNT_PATH",
+        default_value = "/linkerd"
+    )]
+    pub await_mount_path: String,
+
+    /// Name of the linkerd-proxy sidecar container, skipped when patching
+    /// app containers' commands
+    #[clap(
+        long,
+        env = "LINKERD_SWEEP_PROXY_CONTAINER_NAME",
+        default_value = "linkerd-proxy"
+    )]
+    pub proxy_container_name: String,
+
+    /// Port the proxy's admin server listens on, passed to `linkerd-await`
+    /// so it knows where to send the shutdown request
+    #[clap(long, env = "LINKERD_SWEEP_PROXY_ADMIN_PORT", default_value = "4191")]
+    pub proxy_admin_port: u16,
+}
+
+impl Default for InjectionConfig {
+    fn default() -> Self {
+        Self {
+            await_image: "ghcr.io/mateiidavid/await-util:test".to_owned(),
+            await_image_pull_policy: "IfNotPresent".to_owned(),
+            await_mount_path: "/linkerd".to_owned(),
+            proxy_container_name: "linkerd-proxy".to_owned(),
+            proxy_admin_port: 4191,
+        }
+    }
+}
+
 #[derive(Clone)]
-pub struct Admission;
+pub struct Admission {
+    metrics: Arc<Metrics>,
+    injection: InjectionConfig,
+}
 
 impl Service<Request<Body>> for Admission {
     type Response = Response<Body>;
@@ -42,6 +107,8 @@ impl Service<Request<Body>> for Admission {
         // TODO: check methods
         let handler = self.clone();
         Box::pin(async {
+            handler.metrics.admissions_received.inc();
+
             // Turn request body into an AdmissionReview
             let review: AdmissionReview<DynamicObject> = {
                 let body = hyper::body::aggregate(req.into_body()).await?;
@@ -66,18 +133,19 @@ impl Service<Request<Body>> for Admission {
 }
 
 impl Admission {
-    pub fn new() -> Self {
-        Admission
+    pub fn new(metrics: Arc<Metrics>, injection: InjectionConfig) -> Self {
+        Admission { metrics, injection }
     }
 
     // Admit resources:
-    // * Check if DynamicObject is Job. If it is, parse spec and then mutate
+    // * Check if DynamicObject is Pod. If it is, parse spec, and then mutate
+    // * Check if DynamicObject is Job. If it is, parse the PodTemplateSpec out
+    //   of the JobSpec and mutate it the same way, rooted at
+    //   `/spec/template/spec` instead of `/spec`.
     // * Else, resource is unsupported, so admit without mutating
     // * (Low Priority) emit an event whenever an admission is skipped; include
     //  err message
     //  * (Low Priority) Log why admission has been skipped
-    //  * Update: check if DynamicObject is Pod. If it is, parse spec, and then
-    //  mutate
     async fn admit(self, req: AdmissionRequest<DynamicObject>) -> AdmissionResponse {
         if is_kind::<Pod>(&req) {
             let resp = AdmissionResponse::from(&req);
@@ -85,6 +153,7 @@ impl Admission {
                 Ok(v) => v,
                 Err(error) => {
                     debug!(%error, "Error parsing Pod spec");
+                    self.metrics.admission_skipped(SkipReason::ParseError);
                     return resp.deny(error);
                 }
             };
@@ -103,29 +172,63 @@ impl Admission {
                 labels
             } else {
                 debug!("Pod does not contain any labels");
+                self.metrics.admission_skipped(SkipReason::MissingLabel);
                 return resp;
             };
 
-            if !pod_labels.contains_key("extensions.linkerd.io/sweep-sidecar") {
-                debug!(%pod_id, "Pod is missing 'sweep-sidecar' label");
+            if !sweep_enabled(&pod_labels) {
+                debug!(%pod_id, "Skipping pod, 'sweep-sidecar' is missing or not enabled");
+                self.metrics.admission_skipped(SkipReason::NotEnabled);
                 return resp;
-            } else {
-                let enabled = match pod_labels.get("extensions.linkerd.io/sweep-sidecar") {
-                    Some(lv) => lv == "enabled",
-                    None => false,
-                };
-
-                if !enabled {
-                    debug!(%pod_id, "Skipping pod, 'linkerd-sweep' is not enabled");
-                    return resp;
-                }
             }
 
-            self.mutate::<PodSpec>(resp, pod_spec)
+            let injection = self.injection.clone();
+            self.mutate::<PodSpec>(resp, pod_spec, "/spec", injection)
                 .instrument(debug_span!("admission.mutate", %pod_id))
                 .await
+        } else if is_kind::<Job>(&req) {
+            let resp = AdmissionResponse::from(&req);
+            let job_id = JobID::try_from(&req)
+                .unwrap_or_else(|_| JobID("banana-namespace".into(), "banana".into()));
+            let (_job_meta, job_spec) = match parse_spec::<JobSpec>(req) {
+                Ok(v) => v,
+                Err(error) => {
+                    debug!(%error, "Error parsing Job spec");
+                    self.metrics.admission_skipped(SkipReason::ParseError);
+                    return resp.deny(error);
+                }
+            };
+
+            let (template_meta, pod_spec) = match parse_template_spec(job_spec) {
+                Ok(v) => v,
+                Err(error) => {
+                    debug!(%error, "Error parsing Job's PodTemplateSpec");
+                    self.metrics.admission_skipped(SkipReason::ParseError);
+                    return resp.deny(error);
+                }
+            };
+
+            let template_labels = if let Some(labels) = template_meta.labels {
+                labels
+            } else {
+                debug!("Job's pod template does not contain any labels");
+                self.metrics.admission_skipped(SkipReason::MissingLabel);
+                return resp;
+            };
+
+            if !sweep_enabled(&template_labels) {
+                debug!(%job_id, "Skipping job, 'sweep-sidecar' is missing or not enabled on its pod template");
+                self.metrics.admission_skipped(SkipReason::NotEnabled);
+                return resp;
+            }
+
+            let injection = self.injection.clone();
+            self.mutate::<PodSpec>(resp, pod_spec, "/spec/template/spec", injection)
+                .instrument(debug_span!("admission.mutate", %job_id))
+                .await
         } else {
-            debug!("Not pod kind");
+            debug!("Not a Pod or Job");
+            self.metrics.admission_skipped(SkipReason::UnsupportedKind);
             // Unsupported resource
             // admit without mutating
             // print gvk in debug
@@ -133,23 +236,42 @@ impl Admission {
         }
     }
 
-    async fn mutate<T>(self, resp: AdmissionResponse, spec: T) -> AdmissionResponse
+    async fn mutate<T>(
+        self,
+        resp: AdmissionResponse,
+        spec: T,
+        root: &str,
+        injection: InjectionConfig,
+    ) -> AdmissionResponse
     where
         T: Serialize + JsonPatch,
     {
-        match spec.generate_patch() {
-            Ok(patch) => resp
-                .with_patch(patch)
-                .expect("Failed to patch AdmissionResponse"),
+        match spec.generate_patch(root, &injection) {
+            Ok(patch) => {
+                self.metrics.patches_generated.inc();
+                self.metrics.admissions_mutated.inc();
+                resp.with_patch(patch)
+                    .expect("Failed to patch AdmissionResponse")
+            }
 
             Err(err) => {
                 debug!(%err, "Failed to generate patch");
+                self.metrics.patches_failed.inc();
                 resp
             }
         }
     }
 }
 
+/// Whether the `extensions.linkerd.io/sweep-sidecar` gate label is present
+/// and set to `enabled`.
+fn sweep_enabled(labels: &std::collections::BTreeMap<String, String>) -> bool {
+    labels
+        .get("extensions.linkerd.io/sweep-sidecar")
+        .map(|v| v == "enabled")
+        .unwrap_or(false)
+}
+
 fn is_kind<T>(req: &AdmissionRequest<DynamicObject>) -> bool
 where
     T: kube::core::Resource,
@@ -160,27 +282,30 @@ where
 }
 
 trait JsonPatch {
-    fn generate_patch(self) -> Result<json_patch::Patch>;
+    /// Generate the patch operations for this spec, with every path rooted
+    /// at `root` (e.g. `/spec` for a Pod, `/spec/template/spec` for a Job's
+    /// pod template) so the same mutation logic applies to both kinds.
+    fn generate_patch(self, root: &str, injection: &InjectionConfig) -> Result<json_patch::Patch>;
 }
 
 impl JsonPatch for PodSpec {
-    fn generate_patch(self) -> Result<json_patch::Patch> {
+    fn generate_patch(self, root: &str, injection: &InjectionConfig) -> Result<json_patch::Patch> {
         let mut patches: Vec<PatchOperation> = vec![];
 
         if self.init_containers.is_none() {
-            patches.push(mk_add_patch("/spec/initContainers", {}));
+            patches.push(mk_add_patch(format!("{root}/initContainers"), {}));
         }
         patches.push(mk_add_patch(
-            "/spec/initContainers/-",
-            create_curl_container(),
+            format!("{root}/initContainers/-"),
+            create_curl_container(injection),
         ));
 
         if self.volumes.is_none() {
-            patches.push(mk_add_patch("/spec/volumes", {}));
+            patches.push(mk_add_patch(format!("{root}/volumes"), {}));
         }
 
         patches.push(mk_add_patch(
-            "/spec/volumes/-",
+            format!("{root}/volumes/-"),
             k8s_openapi::api::core::v1::Volume {
                 name: "linkerd-await".into(),
                 empty_dir: Some(k8s_openapi::api::core::v1::EmptyDirVolumeSource::default()),
@@ -191,12 +316,12 @@ impl JsonPatch for PodSpec {
         for (i, container) in self.containers.into_iter().enumerate() {
             let name = container.name.clone();
             // skip if proxy
-            if name == "linkerd-proxy" {
+            if name == injection.proxy_container_name {
                 continue;
             }
 
-            let current_path = format!("{}/{}", "/spec/containers", i);
-            match create_container_patches(&current_path, container) {
+            let current_path = format!("{root}/containers/{i}");
+            match create_container_patches(&current_path, container, injection) {
                 Ok(mut container_patches) => {
                     patches.append(&mut container_patches);
                     debug!(container_name=%name, "Patched container");
@@ -215,13 +340,18 @@ impl JsonPatch for PodSpec {
 fn create_container_patches(
     root_path: &str,
     c: k8s_openapi::api::core::v1::Container,
+    injection: &InjectionConfig,
 ) -> Result<Vec<json_patch::PatchOperation>> {
     let comm = c
         .command
         .as_ref()
         .ok_or_else(|| anyhow!("container {} is missing 'command' field", c.name))?;
 
-    let mut new_args = vec!["--shutdown".into(), "--".into()];
+    let mut new_args = vec![
+        "--shutdown".into(),
+        format!("--port={}", injection.proxy_admin_port),
+        "--".into(),
+    ];
     for command in comm.clone().into_iter() {
         new_args.push(command);
     }
@@ -234,7 +364,7 @@ fn create_container_patches(
 
     let mut patches = Vec::new();
     let comm_path = format!("{}/command", root_path);
-    patches.push(mk_replace_patch(comm_path, vec!["/linkerd/linkerd-await"]));
+    patches.push(mk_replace_patch(comm_path, vec![await_binary_path(injection)]));
 
     let arg_path = format!("{}/args", root_path);
     patches.push(mk_replace_patch(arg_path, new_args));
@@ -245,7 +375,10 @@ fn create_container_patches(
     }
 
     let volume_path = format!("{}/volumeMounts/-", root_path);
-    patches.push(mk_add_patch(volume_path, create_volume_mount(true)));
+    patches.push(mk_add_patch(
+        volume_path,
+        create_volume_mount(&injection.await_mount_path, true),
+    ));
 
     Ok(patches)
 }
@@ -335,6 +468,27 @@ impl TryFrom<&ObjectMeta> for PodID {
     }
 }
 
+#[derive(Debug)]
+struct JobID(String, String);
+
+impl std::fmt::Display for JobID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.0, self.1)
+    }
+}
+
+impl TryFrom<&AdmissionRequest<DynamicObject>> for JobID {
+    type Error = anyhow::Error;
+
+    fn try_from(req: &AdmissionRequest<DynamicObject>) -> Result<Self, Self::Error> {
+        let namespace = req
+            .namespace
+            .as_ref()
+            .ok_or_else(|| anyhow!("AdmissionRequest is missing 'namespace'"))?;
+        Ok(JobID(namespace.to_string(), req.name.clone()))
+    }
+}
+
 //
 //////
 //////  Playing around
@@ -364,24 +518,28 @@ fn mk_root_patch<S: Into<String>>(path: S) -> json_patch::PatchOperation {
     })
 }
 
-fn create_volume_mount(read_only: bool) -> k8s_openapi::api::core::v1::VolumeMount {
+fn create_volume_mount(mount_path: &str, read_only: bool) -> k8s_openapi::api::core::v1::VolumeMount {
     k8s_openapi::api::core::v1::VolumeMount {
-        mount_path: "/linkerd".to_owned(),
+        mount_path: mount_path.to_owned(),
         name: "linkerd-await".to_owned(),
         read_only: Some(read_only),
         ..Default::default()
     }
 }
 
-fn create_curl_container() -> k8s_openapi::api::core::v1::Container {
+fn await_binary_path(injection: &InjectionConfig) -> String {
+    format!("{}/linkerd-await", injection.await_mount_path)
+}
+
+fn create_curl_container(injection: &InjectionConfig) -> k8s_openapi::api::core::v1::Container {
     let mut args = vec!["-c".into()];
-    let comm = format!("cp {} {}", "/tmp/linkerd-await", "/linkerd/linkerd-await");
+    let comm = format!("cp {} {}", "/tmp/linkerd-await", await_binary_path(injection));
     args.push(comm);
     k8s_openapi::api::core::v1::Container {
         name: "await-init".to_owned(),
-        image: Some("ghcr.io/mateiidavid/await-util:test".to_owned()),
-        image_pull_policy: Some("IfNotPresent".to_owned()),
-        volume_mounts: Some(vec![create_volume_mount(false)]),
+        image: Some(injection.await_image.clone()),
+        image_pull_policy: Some(injection.await_image_pull_policy.clone()),
+        volume_mounts: Some(vec![create_volume_mount(&injection.await_mount_path, false)]),
         command: Some(vec!["/bin/sh".into()]),
         args: Some(args),
         ..Default::default()
@@ -1,86 +1,179 @@
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{anyhow, Result};
 use futures::{lock::Mutex, prelude::*};
 use hyper::{client, http};
 use k8s_openapi::api::core::v1::{ContainerStatus, Pod};
-use kube::{api::ResourceExt, runtime::watcher::Event};
+use kube::{
+    api::{Api, ResourceExt},
+    runtime::{
+        reflector::{self, Store},
+        watcher::{self, Event},
+        WatchStreamExt,
+    },
+};
+use rand::Rng;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 
-type PodStore = Arc<Mutex<HashSet<PodID>>>;
+use crate::metrics::Metrics;
+
+/// Dedup cache of pods we've already handed off to the sweeper, so a
+/// re-`Applied` event for a pod we've already enqueued doesn't re-send it.
+/// Each entry's [`CancellationToken`] is cancelled if the pod is deleted
+/// before the sweeper's shutdown request completes, so a delete during an
+/// in-flight retry loop doesn't race to shut down a proxy that's already
+/// gone.
+type PodDedup = Arc<Mutex<HashMap<PodID, CancellationToken>>>;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct PodID(String, String);
 
-pub async fn process_pods<S>(events: S, store: PodStore, sender: mpsc::Sender<(PodID, String)>)
-where
-    S: Stream<Item = Event<Pod>>,
+/// Start a `kube::runtime` watcher+reflector pair for the given `Api<Pod>`.
+/// Returns the reflector's `Store<Pod>` (a live, queryable snapshot of every
+/// pod the watch has seen) and the event stream driving it, with backoff
+/// applied to watch errors so a dropped apiserver connection re-lists rather
+/// than terminating the stream.
+pub fn watch_pods(
+    api: Api<Pod>,
+) -> (
+    Store<Pod>,
+    impl Stream<Item = Result<Event<Pod>, watcher::Error>>,
+) {
+    let (reader, writer) = reflector::store();
+    let stream = reflector::reflector(writer, watcher::watcher(api, watcher::Config::default()))
+        .default_backoff();
+    (reader, stream)
+}
+
+pub async fn process_pods<S>(
+    events: S,
+    dedup: PodDedup,
+    sender: mpsc::Sender<(PodID, String, CancellationToken)>,
+) where
+    S: Stream<Item = Result<Event<Pod>, watcher::Error>>,
 {
     tokio::pin!(events);
     while let Some(ev) = events.next().await {
-        handle_pod(ev, store.clone(), sender.clone()).await;
+        match ev {
+            Ok(ev) => handle_pod_event(ev, dedup.clone(), sender.clone()).await,
+            Err(error) => {
+                tracing::warn!(%error, "pod watch error, reflector will back off and re-list")
+            }
+        }
     }
 }
 
-async fn handle_pod(ev: Event<Pod>, store: PodStore, tx: mpsc::Sender<(PodID, String)>) {
+async fn handle_pod_event(
+    ev: Event<Pod>,
+    dedup: PodDedup,
+    tx: mpsc::Sender<(PodID, String, CancellationToken)>,
+) {
     match ev {
-        Event::Applied(pod) => {
-            let pod_id = {
-                let namespace = pod.namespace().unwrap();
-                let name = pod.name();
-                PodID(namespace, name)
-            };
-
-            let injected = pod
-                .annotations()
-                .get("linkerd.io/inject")
-                .and_then(|v| Some(v == "enabled"))
-                .is_some();
-
-            let cached_pods = store.lock().await;
-            if cached_pods.contains(&pod_id) || !injected {
-                tracing::debug!(%pod_id, "skipping pod update");
-                return;
-            } else {
-                drop(cached_pods)
+        Event::Applied(pod) => handle_applied(pod, dedup, tx).await,
+        Event::Deleted(pod) => {
+            let pod_id = pod_id_of(&pod);
+            tracing::debug!(%pod_id, "pod deleted, dropping from dedup cache");
+            if let Some(cancel) = dedup.lock().await.remove(&pod_id) {
+                // A shutdown for this pod may still be in flight (e.g.
+                // retrying); the pod is gone, so there's nothing left to
+                // shut down.
+                cancel.cancel();
+            }
+        }
+        Event::Restarted(pods) => {
+            // The watch reconnected: the reflector's store now holds a fresh
+            // full listing. Reconcile the dedup cache against it so entries
+            // for pods that were deleted while we were disconnected don't
+            // leak forever, and re-evaluate every pod so one that became
+            // ready while we were disconnected is still enqueued (pods
+            // already in the cache are skipped).
+            let seen: HashSet<PodID> = pods.iter().map(pod_id_of).collect();
+            {
+                let mut cached = dedup.lock().await;
+                let stale: Vec<PodID> = cached
+                    .keys()
+                    .filter(|id| !seen.contains(*id))
+                    .cloned()
+                    .collect();
+                for id in stale {
+                    if let Some(cancel) = cached.remove(&id) {
+                        cancel.cancel();
+                    }
+                }
             }
+            for pod in pods {
+                handle_applied(pod, dedup.clone(), tx.clone()).await;
+            }
+        }
+    }
+}
 
-            tracing::info!(%pod_id, "handling pod");
-            let pod_ip = pod.status.and_then(|status| {
-                let has_terminated = status
-                    .container_statuses
-                    .as_ref()
-                    .ok_or(anyhow!("no container statuses found"))
-                    .map(|c| match check_container_terminated(c) {
-                        Err(e) => {
-                            tracing::error!(%pod_id, "error handling pod: {}", e);
-                        }
-                        _ => {}
-                    })
-                    .is_ok();
-                // bit annoying, convert bool to opt to use 'and' combinator for
-                // pod_ip. If container has terminated then we can proceed with
-                // ip
-                has_terminated.then(|| true).and(status.pod_ip)
-            });
+fn pod_id_of(pod: &Pod) -> PodID {
+    let namespace = pod.namespace().unwrap();
+    let name = pod.name();
+    PodID(namespace, name)
+}
 
-            if let Some(ip) = pod_ip {
-                // TODO: add some details here in the trace. we might want to instrument
-                // this whole span to see it clearly
-                tracing::info!(%pod_id, %ip, "sending pod over to sweeper");
-                match tx.send((pod_id.clone(), ip)).await {
-                    Ok(_) => {
-                        tracing::info!("sent event");
-                        let mut cached_pods = store.lock().await;
-                        cached_pods.insert(pod_id);
-                        drop(cached_pods);
-                    }
-                    Err(e) => tracing::error!(%e, "could not send event to sweeper"),
+async fn handle_applied(
+    pod: Pod,
+    dedup: PodDedup,
+    tx: mpsc::Sender<(PodID, String, CancellationToken)>,
+) {
+    let pod_id = pod_id_of(&pod);
+
+    let injected = pod
+        .annotations()
+        .get("linkerd.io/inject")
+        .and_then(|v| Some(v == "enabled"))
+        .is_some();
+
+    let cached_pods = dedup.lock().await;
+    if cached_pods.contains_key(&pod_id) || !injected {
+        tracing::debug!(%pod_id, "skipping pod update");
+        return;
+    } else {
+        drop(cached_pods)
+    }
+
+    tracing::info!(%pod_id, "handling pod");
+    let pod_ip = pod.status.and_then(|status| {
+        let has_terminated = status
+            .container_statuses
+            .as_ref()
+            .ok_or(anyhow!("no container statuses found"))
+            .map(|c| match check_container_terminated(c) {
+                Err(e) => {
+                    tracing::error!(%pod_id, "error handling pod: {}", e);
                 }
-                // send over mpsc
+                _ => {}
+            })
+            .is_ok();
+        // bit annoying, convert bool to opt to use 'and' combinator for
+        // pod_ip. If container has terminated then we can proceed with
+        // ip
+        has_terminated.then(|| true).and(status.pod_ip)
+    });
+
+    if let Some(ip) = pod_ip {
+        // TODO: add some details here in the trace. we might want to instrument
+        // this whole span to see it clearly
+        tracing::info!(%pod_id, %ip, "sending pod over to sweeper");
+        let cancel = CancellationToken::new();
+        match tx.send((pod_id.clone(), ip, cancel.clone())).await {
+            Ok(_) => {
+                tracing::info!("sent event");
+                let mut cached_pods = dedup.lock().await;
+                cached_pods.insert(pod_id, cancel);
+                drop(cached_pods);
             }
+            Err(e) => tracing::error!(%e, "could not send event to sweeper"),
         }
-        _ => {}
+        // send over mpsc
     }
 }
 
@@ -109,54 +202,161 @@ fn check_container_terminated(containers: &Vec<ContainerStatus>) -> Result<()> {
     Err(anyhow!(format!("no terminated containers found")))
 }
 
+/// Shutdown timeout/retry knobs for the [`Sweeper`], shared across CLI and env.
+#[derive(clap::Args, Debug, Clone, Copy)]
+pub struct SweepConfig {
+    /// Timeout for a single proxy shutdown request
+    #[clap(
+        long,
+        env = "LINKERD_SWEEP_SHUTDOWN_TIMEOUT",
+        default_value = "1s"
+    )]
+    pub shutdown_timeout: humantime::Duration,
+
+    /// Maximum number of retries before giving up on a proxy shutdown request
+    #[clap(
+        long,
+        env = "LINKERD_SWEEP_SHUTDOWN_MAX_RETRIES",
+        default_value = "5"
+    )]
+    pub shutdown_max_retries: u32,
+}
+
+impl Default for SweepConfig {
+    fn default() -> Self {
+        Self {
+            shutdown_timeout: Duration::from_secs(1).into(),
+            shutdown_max_retries: 5,
+        }
+    }
+}
+
 pub struct Sweeper {
     client: hyper::Client<client::HttpConnector>,
-    rx: mpsc::Receiver<(PodID, String)>,
-    store: PodStore,
+    rx: mpsc::Receiver<(PodID, String, CancellationToken)>,
+    store: PodDedup,
+    config: SweepConfig,
+    metrics: Arc<Metrics>,
 }
 
 impl Sweeper {
     pub fn new(
         client: hyper::Client<client::HttpConnector>,
-        rx: mpsc::Receiver<(PodID, String)>,
-        store: PodStore,
+        rx: mpsc::Receiver<(PodID, String, CancellationToken)>,
+        store: PodDedup,
+        config: SweepConfig,
+        metrics: Arc<Metrics>,
     ) -> Self {
-        Self { client, rx, store }
+        Self {
+            client,
+            rx,
+            store,
+            config,
+            metrics,
+        }
     }
 
     pub async fn run(mut self, port: u16) -> Result<()> {
         while let Some(job) = self.rx.recv().await {
-            let (id, ip) = job;
+            let (id, ip, cancel) = job;
             let shutdown_endpoint = format!("{}:{}", ip, &port);
             let client = self.client.clone();
             let pod_store = self.store.clone();
+            let config = self.config;
+            let metrics = self.metrics.clone();
             tokio::spawn(async move {
-                let req = {
-                    let uri = hyper::Uri::builder()
-                        .scheme(http::uri::Scheme::HTTP)
-                        .authority(shutdown_endpoint)
-                        .path_and_query("/shutdown")
-                        .build()
-                        .unwrap();
-                    http::Request::builder()
-                        .method(http::Method::POST)
-                        .uri(uri)
-                        .body(Default::default())
-                        .expect("shutdown request must be valid")
-                };
-
-                tracing::info!(%id, %ip, "sending shutdown request");
-                let resp = client.request(req).await.expect("failed");
-                tracing::info!(%ip, "shutdown sent");
-                let status = resp.status();
-                tracing::info!(%status, "status");
-                pod_store.lock().await.remove(&id);
+                let start = std::time::Instant::now();
+                metrics.shutdown_requests_sent.inc();
+                tokio::select! {
+                    _ = cancel.cancelled() => {
+                        // The pod was deleted while this shutdown was in
+                        // flight; there's no proxy left to shut down.
+                        tracing::debug!(%id, %ip, "shutdown request cancelled, pod deleted");
+                    }
+                    result = send_shutdown(&client, &shutdown_endpoint, config) => {
+                        match result {
+                            Ok(status) => {
+                                tracing::info!(%id, %ip, %status, "shutdown sent");
+                                metrics.shutdown_requests_succeeded.inc();
+                                metrics
+                                    .shutdown_latency_seconds
+                                    .observe(start.elapsed().as_secs_f64());
+                                pod_store.lock().await.remove(&id);
+                            }
+                            Err(error) => {
+                                metrics.shutdown_requests_failed.inc();
+                                // Drop the pod from the store: `handle_applied` skips
+                                // ids already present, so leaving this one in would
+                                // permanently mark it handled and the proxy would
+                                // never be swept. Removing it lets a later Applied
+                                // event (or reconcile) retry the shutdown.
+                                pod_store.lock().await.remove(&id);
+                                tracing::error!(%id, %ip, %error, "giving up on shutdown request");
+                            }
+                        }
+                    }
+                }
             });
         }
         Ok(())
     }
 }
 
+/// POST `/shutdown` to the proxy admin endpoint, retrying transient failures
+/// (timeouts and non-2xx responses) with exponential backoff and jitter.
+async fn send_shutdown(
+    client: &hyper::Client<client::HttpConnector>,
+    shutdown_endpoint: &str,
+    config: SweepConfig,
+) -> Result<http::StatusCode> {
+    let base: Duration = config.shutdown_timeout.into();
+    let mut attempt = 0u32;
+    loop {
+        let req = http::Request::builder()
+            .method(http::Method::POST)
+            .uri(
+                hyper::Uri::builder()
+                    .scheme(http::uri::Scheme::HTTP)
+                    .authority(shutdown_endpoint)
+                    .path_and_query("/shutdown")
+                    .build()
+                    .unwrap(),
+            )
+            .body(Default::default())
+            .expect("shutdown request must be valid");
+
+        tracing::info!(%shutdown_endpoint, attempt, "sending shutdown request");
+        let outcome = tokio::time::timeout(base, client.request(req)).await;
+        match outcome {
+            Ok(Ok(resp)) if resp.status().is_success() => return Ok(resp.status()),
+            Ok(Ok(resp)) => {
+                tracing::debug!(%shutdown_endpoint, status = %resp.status(), attempt, "shutdown request failed")
+            }
+            Ok(Err(error)) => {
+                tracing::debug!(%shutdown_endpoint, %error, attempt, "shutdown request errored")
+            }
+            Err(_) => {
+                tracing::debug!(%shutdown_endpoint, ?base, attempt, "shutdown request timed out")
+            }
+        }
+
+        if attempt >= config.shutdown_max_retries {
+            return Err(anyhow!(
+                "exhausted {} retries sending shutdown request to {}",
+                config.shutdown_max_retries,
+                shutdown_endpoint
+            ));
+        }
+        attempt += 1;
+
+        let backoff = base.saturating_mul(1 << attempt.min(6));
+        let jitter = rand::thread_rng().gen_range(Duration::ZERO..=base);
+        let backoff = backoff.saturating_add(jitter).min(Duration::from_secs(30));
+        tracing::debug!(?backoff, attempt, "backing off before retry");
+        tokio::time::sleep(backoff).await;
+    }
+}
+
 impl std::fmt::Display for PodID {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}/{}", self.0, self.1)
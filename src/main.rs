@@ -1,7 +1,8 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, path::PathBuf};
 
 use anyhow::Result;
 use clap::Parser;
+use linkerd_sweep::admission::InjectionConfig;
 use linkerd_sweep::server::AdmissionServer;
 use tracing::info;
 
@@ -19,6 +20,15 @@ struct Args {
     /// Log format (json | plain)
     #[clap(long, env = "LINKERD_SWEEP_LOG_FORMAT", default_value = "plain")]
     log_format: kubert::LogFormat,
+
+    /// CA bundle used to verify client certificates presented by callers
+    /// (e.g. the Kubernetes API server). When unset, the webhook accepts
+    /// connections from any client.
+    #[clap(long, env = "LINKERD_SWEEP_CLIENT_CA")]
+    client_ca: Option<PathBuf>,
+
+    #[clap(flatten)]
+    injection: InjectionConfig,
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -26,6 +36,8 @@ async fn main() -> Result<()> {
     let Args {
         log_level,
         log_format,
+        client_ca,
+        injection,
     } = Args::parse();
 
     log_format.try_init(log_level)?;
@@ -33,7 +45,9 @@ async fn main() -> Result<()> {
     let (_shutdown_tx, shutdown_rx) = kubert::shutdown::sigint_or_sigterm()?;
 
     let listen_addr = SocketAddr::from(([0, 0, 0, 0], 443));
-    let server = AdmissionServer::new(listen_addr, shutdown_rx.clone());
+    let server = AdmissionServer::new(listen_addr, shutdown_rx.clone())
+        .with_client_ca(client_ca)
+        .with_injection_config(injection);
     let server_task = tokio::spawn(server.run());
     tokio::select! {
         _ = shutdown_rx.signaled() => {
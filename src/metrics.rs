@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use hyper::{server::conn::Http, service::service_fn, Body, Request, Response};
+use prometheus_client::encoding::text::encode;
+use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::histogram::Histogram;
+use prometheus_client::registry::Registry;
+use tokio::net::TcpListener;
+use tracing::{error, info};
+
+/// Why an admission request was skipped without mutating the resource.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum SkipReason {
+    MissingLabel,
+    NotEnabled,
+    ParseError,
+    UnsupportedKind,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct SkipLabels {
+    reason: SkipReason,
+}
+
+/// Counters and histograms for admission and sweep activity, registered
+/// against a single [`Registry`] and exposed in Prometheus text format on
+/// `/metrics`.
+#[derive(Clone)]
+pub struct Metrics {
+    pub admissions_received: Counter,
+    pub admissions_mutated: Counter,
+    admissions_skipped: Family<SkipLabels, Counter>,
+    pub patches_generated: Counter,
+    pub patches_failed: Counter,
+    pub shutdown_requests_sent: Counter,
+    pub shutdown_requests_succeeded: Counter,
+    pub shutdown_requests_failed: Counter,
+    pub shutdown_latency_seconds: Histogram,
+}
+
+impl Metrics {
+    pub fn register(registry: &mut Registry) -> Self {
+        let admissions_received = Counter::default();
+        registry.register(
+            "admissions_received",
+            "Total admission requests received",
+            admissions_received.clone(),
+        );
+
+        let admissions_mutated = Counter::default();
+        registry.register(
+            "admissions_mutated",
+            "Total admission requests that were mutated",
+            admissions_mutated.clone(),
+        );
+
+        let admissions_skipped = Family::<SkipLabels, Counter>::default();
+        registry.register(
+            "admissions_skipped",
+            "Total admission requests not mutated (admitted unchanged, or denied on a parse error), by reason",
+            admissions_skipped.clone(),
+        );
+
+        let patches_generated = Counter::default();
+        registry.register(
+            "patches_generated",
+            "Total JSON patches successfully generated",
+            patches_generated.clone(),
+        );
+
+        let patches_failed = Counter::default();
+        registry.register(
+            "patches_failed",
+            "Total JSON patches that failed to generate",
+            patches_failed.clone(),
+        );
+
+        let shutdown_requests_sent = Counter::default();
+        registry.register(
+            "shutdown_requests_sent",
+            "Total proxy shutdown requests sent",
+            shutdown_requests_sent.clone(),
+        );
+
+        let shutdown_requests_succeeded = Counter::default();
+        registry.register(
+            "shutdown_requests_succeeded",
+            "Total proxy shutdown requests that succeeded",
+            shutdown_requests_succeeded.clone(),
+        );
+
+        let shutdown_requests_failed = Counter::default();
+        registry.register(
+            "shutdown_requests_failed",
+            "Total proxy shutdown requests that ultimately failed",
+            shutdown_requests_failed.clone(),
+        );
+
+        let shutdown_latency_seconds = Histogram::new(
+            [0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0].into_iter(),
+        );
+        registry.register(
+            "shutdown_latency_seconds",
+            "Round-trip latency of proxy shutdown requests",
+            shutdown_latency_seconds.clone(),
+        );
+
+        Self {
+            admissions_received,
+            admissions_mutated,
+            admissions_skipped,
+            patches_generated,
+            patches_failed,
+            shutdown_requests_sent,
+            shutdown_requests_succeeded,
+            shutdown_requests_failed,
+            shutdown_latency_seconds,
+        }
+    }
+
+    pub fn admission_skipped(&self, reason: SkipReason) {
+        self.admissions_skipped.get_or_create(&SkipLabels { reason }).inc();
+    }
+}
+
+/// Serve the registry's metrics as Prometheus text format on `addr`, on a
+/// separate plaintext port from the TLS admission listener.
+pub async fn serve(addr: std::net::SocketAddr, registry: Arc<Registry>) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("failed to bind metrics listener on {addr}"))?;
+    info!(%addr, "Serving metrics");
+
+    loop {
+        let (socket, _) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                error!(%err, "Failed to accept metrics connection");
+                continue;
+            }
+        };
+
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            let registry = registry.clone();
+            let service = service_fn(move |_req: Request<Body>| {
+                let registry = registry.clone();
+                async move {
+                    let mut buf = String::new();
+                    let body = match encode(&mut buf, &registry) {
+                        Ok(()) => Body::from(buf),
+                        Err(err) => {
+                            error!(%err, "Failed to encode metrics");
+                            Body::from("failed to encode metrics")
+                        }
+                    };
+                    Ok::<_, hyper::Error>(
+                        Response::builder()
+                            .header(
+                                hyper::header::CONTENT_TYPE,
+                                "application/openmetrics-text; version=1.0.0; charset=utf-8",
+                            )
+                            .body(body)
+                            .unwrap(),
+                    )
+                }
+            });
+
+            if let Err(err) = Http::new().serve_connection(socket, service).await {
+                error!(%err, "Failed to serve metrics connection");
+            }
+        });
+    }
+}
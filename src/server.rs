@@ -1,20 +1,30 @@
 
-use std::{net::SocketAddr, path::PathBuf};
+use std::{
+    net::{Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+};
 
-use crate::admission::Admission;
+use crate::admission::{Admission, InjectionConfig};
+use crate::metrics::{self, Metrics};
 use crate::tls;
 use anyhow::{bail, Context, Result};
+use futures::future::select_all;
 use hyper::server::conn::Http;
 use kubert::shutdown;
+use prometheus_client::registry::Registry;
 use tokio::net::{TcpListener, TcpStream};
-use tracing::{debug_span, error, info, Instrument};
+use tracing::{debug, debug_span, error, info, warn, Instrument};
 
 #[derive(Debug)]
 pub struct AdmissionServer {
     bind_addr: SocketAddr,
+    metrics_addr: SocketAddr,
     shutdown: shutdown::Watch,
     cert_path: PathBuf,
     key_path: PathBuf,
+    client_ca_path: Option<PathBuf>,
+    injection: InjectionConfig,
 }
 
 
@@ -22,39 +32,125 @@ impl AdmissionServer {
     pub fn new(bind_addr: SocketAddr, shutdown: shutdown::Watch) -> Self {
         AdmissionServer {
             bind_addr,
+            metrics_addr: SocketAddr::from(([0, 0, 0, 0], 9000)),
             shutdown,
             cert_path: PathBuf::from("/var/run/sweep/tls.crt"),
             key_path: PathBuf::from("/var/run/sweep/tls.key"),
+            client_ca_path: None,
+            injection: InjectionConfig::default(),
         }
     }
 
+    /// Require and verify client certificates signed by the given CA bundle,
+    /// restricting the webhook to connections presenting a cert signed by
+    /// the cluster CA (e.g. the Kubernetes API server's).
+    pub fn with_client_ca(mut self, client_ca_path: Option<PathBuf>) -> Self {
+        self.client_ca_path = client_ca_path;
+        self
+    }
+
+    /// Override the defaults for the injected sidecar (await-util image,
+    /// proxy container name, proxy admin port).
+    pub fn with_injection_config(mut self, injection: InjectionConfig) -> Self {
+        self.injection = injection;
+        self
+    }
+
     #[tracing::instrument(level = "info", skip_all)]
     pub async fn run(self) -> Result<()> {
-        let listener = TcpListener::bind(&self.bind_addr)
+        let listeners = Self::bind_dual_stack(self.bind_addr)
             .await
-            .expect("Failed to bind listener");
+            .context("failed to bind admission webhook listener")?;
 
-        let accept_task = tokio::spawn(AdmissionServer::accept(
-            listener,
+        let acceptor = tls::ReloadingAcceptor::spawn(
             self.cert_path.clone(),
             self.key_path.clone(),
-        ));
+            self.client_ca_path.clone(),
+        )
+        .await
+        .context("failed to build TLS acceptor")?;
+
+        let mut registry = Registry::default();
+        let metrics = Arc::new(Metrics::register(&mut registry));
+        let registry = Arc::new(registry);
+
+        let metrics_task = tokio::spawn(metrics::serve(self.metrics_addr, registry));
+        let accept_tasks = listeners.into_iter().map(|listener| {
+            tokio::spawn(AdmissionServer::accept(
+                listener,
+                acceptor.clone(),
+                metrics.clone(),
+                self.injection.clone(),
+            ))
+        });
+        let accept_tasks = select_all(accept_tasks);
 
         tokio::select! {
             _ = self.shutdown.signaled() => {
                 info!("Received shutdown signal");
                 return Ok(());
             }
-            _ = accept_task => {},
+            _ = accept_tasks => {},
+            _ = metrics_task => {},
         }
 
         Ok(())
     }
 
+    /// Bind the admission webhook's listening socket on both address
+    /// families: an IPv6 socket (which, on most Linux configurations, also
+    /// accepts IPv4 connections via v4-mapped addresses) and a plain IPv4
+    /// socket as a fallback for hosts where IPv6 is unavailable or
+    /// `net.ipv6.bindv6only` is set, in which case both sockets are needed.
+    /// Binding the IPv4 socket after the IPv6 one can fail with
+    /// `EADDRINUSE` when the IPv6 socket already covers it; that specific
+    /// error is tolerated as long as the IPv6 socket bound. Any other IPv4
+    /// bind error (permissions, address not available, ...) is a real
+    /// misconfiguration and is still surfaced.
+    async fn bind_dual_stack(addr: SocketAddr) -> Result<Vec<TcpListener>> {
+        let port = addr.port();
+        let mut listeners = Vec::with_capacity(2);
+
+        let v6_addr = SocketAddr::from((Ipv6Addr::UNSPECIFIED, port));
+        match TcpListener::bind(v6_addr).await {
+            Ok(listener) => {
+                info!(%v6_addr, "listening");
+                listeners.push(listener);
+            }
+            Err(error) => warn!(%v6_addr, %error, "failed to bind IPv6 listener"),
+        }
+
+        match TcpListener::bind(addr).await {
+            Ok(listener) => {
+                info!(%addr, "listening");
+                listeners.push(listener);
+            }
+            Err(error) if listeners.is_empty() => {
+                return Err(error).with_context(|| format!("failed to bind {addr}"))
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::AddrInUse => {
+                debug!(%addr, %error, "IPv4 bind failed, relying on the dual-stack IPv6 socket");
+            }
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to bind {addr}"))
+            }
+        }
+
+        if listeners.is_empty() {
+            bail!("failed to bind admission webhook listener on port {port}");
+        }
+        Ok(listeners)
+    }
+
     /// Accept loop. Figure out how to gracefully wait until all conns have
     /// finished
     #[tracing::instrument(level = "info", skip_all)]
-    async fn accept(listener: TcpListener, cert_path: PathBuf, key_path: PathBuf) {
+    async fn accept(
+        listener: TcpListener,
+        acceptor: tls::ReloadingAcceptor,
+        metrics: Arc<Metrics>,
+        injection: InjectionConfig,
+    ) {
         loop {
             let (socket, peer_addr) = match listener.accept().await {
                 Ok((socket, addr)) => {
@@ -67,33 +163,35 @@ impl AdmissionServer {
                 }
             };
 
-            tokio::spawn(Self::handle_conn(socket, peer_addr, cert_path.clone(), key_path.clone()));
+            tokio::spawn(Self::handle_conn(
+                socket,
+                peer_addr,
+                acceptor.clone(),
+                metrics.clone(),
+                injection.clone(),
+            ));
         }
     }
 
     #[tracing::instrument(
-        level = "info", 
-        skip(socket, cert_path, key_path), 
+        level = "info",
+        skip(socket, acceptor, metrics, injection),
         fields(client.addr = %client_addr))]
     async fn handle_conn(
         socket: TcpStream,
         client_addr: SocketAddr,
-        cert_path: PathBuf,
-        key_path: PathBuf,
+        acceptor: tls::ReloadingAcceptor,
+        metrics: Arc<Metrics>,
+        injection: InjectionConfig,
     ) -> Result<()> {
-        // Build TLS Connector
-        let tls = match tls::mk_tls_connector(&cert_path, &key_path).await {
-            Ok(tls) => tls,
-            Err(error) => {
-                error!(%error, "Failed to establish TLS connection");
-                bail!("Failed to establish TLS connection: {}", error);
-            }
-        };
+        // Grab the currently-active TLS acceptor; rotation happens in the
+        // background, so this is just a cheap clone, not a disk read.
+        let tls = acceptor.acceptor();
 
         // Build TLS conn
         let stream = tls.accept(socket).await.with_context(|| "TLS Error")?;
         match Http::new()
-            .serve_connection(stream, Admission::new())
+            .serve_connection(stream, Admission::new(metrics, injection))
             .instrument(debug_span!("admission"))
             .await
         {
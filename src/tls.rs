@@ -1,7 +1,17 @@
 use anyhow::{anyhow, Context, Result};
-use std::{io::BufReader, path::PathBuf, sync::Arc};
+use arc_swap::ArcSwap;
+use std::{io::BufReader, path::PathBuf, sync::Arc, time::Duration};
 use tokio::fs;
 
+/// Fallback poll interval for filesystem backends where the notify watcher
+/// misses an atomic-rename mount (e.g. some network filesystems).
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long to wait after the first filesystem event before reloading, so a
+/// burst of renames from a single secret projection update is coalesced into
+/// one reload instead of several.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
 pub async fn load_certificate(path: &PathBuf) -> Result<Vec<rustls::Certificate>> {
     tracing::trace!(?path, "loading certificate");
     let file = fs::read(path).await?;
@@ -18,25 +28,65 @@ pub async fn load_certificate(path: &PathBuf) -> Result<Vec<rustls::Certificate>
     Ok(certs.into_iter().map(rustls::Certificate).collect())
 }
 
+/// Load a private key regardless of its encoding: PKCS#8, EC (SEC1), or
+/// traditional RSA. Certificates issued by cert-manager or most modern CAs
+/// are PKCS#8-wrapped or ECDSA, so we can't assume RSA up front.
 pub async fn load_keys(path: &PathBuf) -> Result<rustls::PrivateKey> {
     tracing::trace!(?path, "loading private key");
     let file = fs::read(path).await?;
     let mut reader = BufReader::new(file.as_slice());
 
-    let mut keys = rustls_pemfile::rsa_private_keys(&mut reader)
-        .with_context(|| "could not read private key")?;
-    if keys.len() != 1 {
-        tracing::trace!(?path, "number of mounted private keys: {}", keys.len());
-        return Err(anyhow!("expected only one private key in file {:?}", &path));
+    loop {
+        match rustls_pemfile::read_one(&mut reader)
+            .with_context(|| "could not read private key")?
+        {
+            Some(rustls_pemfile::Item::PKCS8Key(key))
+            | Some(rustls_pemfile::Item::ECKey(key))
+            | Some(rustls_pemfile::Item::RSAKey(key)) => return Ok(rustls::PrivateKey(key)),
+            Some(_) => continue,
+            None => {
+                return Err(anyhow!("no private key found in file {:?}", &path));
+            }
+        }
     }
+}
 
-    Ok(rustls::PrivateKey(keys.remove(0)))
+/// Load a CA bundle into a `RootCertStore`, for verifying client certificates
+/// presented by the calling API server.
+pub async fn load_ca_bundle(path: &PathBuf) -> Result<rustls::RootCertStore> {
+    let certs = load_certificate(path)
+        .await
+        .with_context(|| "failed to load CA bundle")?;
+
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in certs {
+        roots
+            .add(&cert)
+            .with_context(|| format!("invalid CA certificate in {:?}", path))?;
+    }
+    Ok(roots)
+}
+
+/// Build a `CertifiedKey` from the cert/key on disk, picking the right
+/// signing-key implementation for whatever key type was loaded (RSA, PKCS#8,
+/// or EC).
+async fn load_certified_key(cert_path: &PathBuf, key_path: &PathBuf) -> Result<rustls::sign::CertifiedKey> {
+    let certs = load_certificate(cert_path)
+        .await
+        .with_context(|| "failed to load certificate")?;
+    let private_key = load_keys(key_path)
+        .await
+        .with_context(|| "failed to load private key")?;
+    let signing_key = rustls::sign::any_supported_type(&private_key)
+        .map_err(|_| anyhow!("unsupported private key type in {:?}", key_path))?;
+    Ok(rustls::sign::CertifiedKey::new(certs, signing_key))
 }
 
 #[tracing::instrument]
 pub async fn mk_tls_connector(
     cert_path: &PathBuf,
     key_path: &PathBuf,
+    client_ca: Option<&PathBuf>,
 ) -> Result<tokio_rustls::TlsAcceptor> {
     let certs = load_certificate(cert_path)
         .await
@@ -45,12 +95,184 @@ pub async fn mk_tls_connector(
         .await
         .with_context(|| "failed to load private key")?;
 
-    let mut cfg = rustls::ServerConfig::builder()
-        .with_safe_defaults()
-        .with_no_client_auth()
+    let builder = rustls::ServerConfig::builder().with_safe_defaults();
+    let builder = if let Some(ca_path) = client_ca {
+        let roots = load_ca_bundle(ca_path).await?;
+        builder.with_client_cert_verifier(rustls::server::AllowAnyAuthenticatedClient::new(roots))
+    } else {
+        builder.with_no_client_auth()
+    };
+
+    let mut cfg = builder
         .with_single_cert(certs, private_key)
         .expect("bad certificate/key");
     cfg.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
     let tls_acceptor = tokio_rustls::TlsAcceptor::from(Arc::from(cfg));
     Ok(tls_acceptor)
 }
+
+/// A `rustls::server::ResolvesServerCert` backed by an `ArcSwap`, so the
+/// background reload task can atomically publish a freshly-loaded
+/// `CertifiedKey` without the `ServerConfig`, and therefore the
+/// `TlsAcceptor`, ever needing to be rebuilt.
+struct CertResolver {
+    current: ArcSwap<rustls::sign::CertifiedKey>,
+}
+
+impl std::fmt::Debug for CertResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertResolver").finish()
+    }
+}
+
+impl rustls::server::ResolvesServerCert for CertResolver {
+    fn resolve(
+        &self,
+        _client_hello: rustls::server::ClientHello<'_>,
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        Some(self.current.load_full())
+    }
+}
+
+/// A `TlsAcceptor` whose serving certificate is swapped in the background
+/// whenever the mounted cert/key change, so rotated certs (e.g. from
+/// cert-manager) get picked up without a pod restart and without rebuilding
+/// the `ServerConfig` on every rotation.
+#[derive(Clone)]
+pub struct ReloadingAcceptor {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    resolver: Arc<CertResolver>,
+    acceptor: tokio_rustls::TlsAcceptor,
+}
+
+impl ReloadingAcceptor {
+    /// Build the initial acceptor and spawn the background reload task.
+    pub async fn spawn(
+        cert_path: PathBuf,
+        key_path: PathBuf,
+        client_ca: Option<PathBuf>,
+    ) -> Result<Self> {
+        let certified_key = load_certified_key(&cert_path, &key_path).await?;
+        let resolver = Arc::new(CertResolver {
+            current: ArcSwap::from_pointee(certified_key),
+        });
+
+        let builder = rustls::ServerConfig::builder().with_safe_defaults();
+        let builder = if let Some(ca_path) = &client_ca {
+            let roots = load_ca_bundle(ca_path).await?;
+            builder.with_client_cert_verifier(rustls::server::AllowAnyAuthenticatedClient::new(roots))
+        } else {
+            builder.with_no_client_auth()
+        };
+        let mut cfg = builder.with_cert_resolver(resolver.clone());
+        cfg.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(cfg));
+
+        let this = Self {
+            cert_path,
+            key_path,
+            resolver,
+            acceptor,
+        };
+        tokio::spawn(this.clone().reload_loop());
+        Ok(this)
+    }
+
+    /// Clone the (fixed) `TlsAcceptor` for a single connection. Rotation
+    /// happens inside the cert resolver, so this never changes.
+    pub fn acceptor(&self) -> tokio_rustls::TlsAcceptor {
+        self.acceptor.clone()
+    }
+
+    #[tracing::instrument(level = "info", skip(self), fields(cert = ?self.cert_path, key = ?self.key_path))]
+    async fn reload_loop(self) {
+        let (notify_tx, mut notify_rx) = tokio::sync::mpsc::channel::<()>(16);
+        if let Err(error) = spawn_fs_watcher(&self.cert_path, &self.key_path, notify_tx) {
+            tracing::warn!(%error, "failed to start cert/key file watcher, falling back to polling only");
+        }
+
+        let mut poll = tokio::time::interval(RELOAD_POLL_INTERVAL);
+        poll.tick().await; // the first tick fires immediately; skip it
+        let mut watching = true;
+
+        loop {
+            tokio::select! {
+                got = notify_rx.recv(), if watching => {
+                    if got.is_none() {
+                        // Watcher thread died; keep relying on the fallback poll.
+                        watching = false;
+                        continue;
+                    }
+                    // Debounce: coalesce a burst of events from a single
+                    // secret-projection update into one reload.
+                    tokio::time::sleep(RELOAD_DEBOUNCE).await;
+                    while notify_rx.try_recv().is_ok() {}
+                    tracing::info!("detected cert/key change, reloading");
+                }
+                _ = poll.tick() => {
+                    tracing::trace!("periodic cert/key reload check");
+                }
+            }
+
+            match load_certified_key(&self.cert_path, &self.key_path).await {
+                Ok(certified_key) => {
+                    self.resolver.current.store(Arc::new(certified_key));
+                    tracing::info!("reloaded TLS certificate");
+                }
+                Err(error) => {
+                    tracing::error!(%error, "failed to reload TLS certificate, keeping previous one");
+                }
+            }
+        }
+    }
+}
+
+/// Spawn a blocking thread running an inotify (or platform-equivalent)
+/// watcher on the cert/key files' parent directory, forwarding a
+/// notification on every filesystem event. Watching the directory rather
+/// than the files directly is what lets this survive the atomic-rename
+/// mounts Kubernetes uses for secret projections, where the watched inode
+/// itself is replaced.
+fn spawn_fs_watcher(
+    cert_path: &PathBuf,
+    key_path: &PathBuf,
+    tx: tokio::sync::mpsc::Sender<()>,
+) -> Result<()> {
+    use notify::Watcher;
+
+    let watch_dir = cert_path
+        .parent()
+        .map(ToOwned::to_owned)
+        .unwrap_or_else(|| PathBuf::from("."));
+    let key_dir = key_path.parent().map(ToOwned::to_owned);
+
+    std::thread::spawn(move || {
+        let (std_tx, std_rx) = std::sync::mpsc::channel();
+        let mut watcher = match notify::recommended_watcher(std_tx) {
+            Ok(watcher) => watcher,
+            Err(error) => {
+                tracing::error!(%error, "failed to create cert/key watcher");
+                return;
+            }
+        };
+
+        if let Err(error) = watcher.watch(&watch_dir, notify::RecursiveMode::NonRecursive) {
+            tracing::error!(%error, ?watch_dir, "failed to watch cert directory");
+            return;
+        }
+        if let Some(key_dir) = key_dir.filter(|d| *d != watch_dir) {
+            if let Err(error) = watcher.watch(&key_dir, notify::RecursiveMode::NonRecursive) {
+                tracing::error!(%error, ?key_dir, "failed to watch key directory");
+            }
+        }
+
+        for event in std_rx {
+            if event.is_ok() && tx.blocking_send(()).is_err() {
+                return;
+            }
+        }
+    });
+
+    Ok(())
+}